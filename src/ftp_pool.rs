@@ -0,0 +1,219 @@
+use crate::ftp_sender::FtpSender;
+use ftp::{FtpError, FtpStream};
+use log::{info, warn};
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex};
+
+/// プールが内部で保持する状態。`idle` は再利用可能な接続、`in_use` は貸し出し中の接続数
+struct PoolState {
+    idle: Vec<FtpStream>,
+    in_use: usize,
+}
+
+/// 認証済みの `FtpStream` を複数保持し、使い回すための接続プール
+///
+/// 毎回 connect + login + quit を行う代わりに、設定した数までの接続を維持し、
+/// `send_file`/`get_file` 呼び出し時に使い回す。貸し出した接続が切断済みだった場合は
+/// 透過的に再接続・再ログインしたうえで 1 度だけ処理を再試行する
+pub struct FtpPool {
+    sender: FtpSender,
+    capacity: usize,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+impl FtpPool {
+    /// 最大 `capacity` 本までの接続を保持するプールを作成する
+    pub fn new(sender: FtpSender, capacity: usize) -> Self {
+        FtpPool {
+            sender,
+            capacity: capacity.max(1),
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                in_use: 0,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    /// 指定されたファイルをプールの接続を使って送信する
+    pub fn send_file(
+        &self,
+        source_file_path: &str,
+        target_folder: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.with_connection(|ftp_stream| {
+            self.sender
+                .send_file_over(ftp_stream, source_file_path, target_folder, None)
+        })
+    }
+
+    /// 指定されたリモートファイルをプールの接続を使ってダウンロードする
+    pub fn get_file(&self, remote_path: &str, local_folder: &str) -> Result<(), Box<dyn Error>> {
+        self.with_connection(|ftp_stream| {
+            self.sender.get_file_over(ftp_stream, remote_path, local_folder)
+        })
+    }
+
+    /// 複数のファイルをプールの接続に振り分けて送信する。
+    /// `items` は (送信元パス, 送信先フォルダー) の組。
+    /// アイテムごとにスレッドを立てて並行に送信し、`acquire` のブロッキングにより
+    /// 実際の同時実行数はプールの `capacity` に自然と制限される。結果は `items` と同じ順序で返す
+    pub fn send_files(&self, items: &[(PathBuf, String)]) -> Vec<Result<(), Box<dyn Error>>> {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = items
+                .iter()
+                .map(|(source_path, target_folder)| {
+                    scope.spawn(move || {
+                        self.send_file(&source_path.to_string_lossy(), target_folder)
+                            .map_err(|e| e.to_string())
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err("Pooled upload thread panicked".to_string()))
+                        .map_err(|e| -> Box<dyn Error> { e.into() })
+                })
+                .collect()
+        })
+    }
+
+    /// プールから接続を借り受け、`op` を実行する。接続自体が失効・切断されたことを示す
+    /// エラーだった場合に限り、破棄して新しい接続で 1 度だけ再試行する。ファイルが存在しない
+    /// といった接続には無関係のエラーでは、借りた接続を壊さずそのままプールへ返却する
+    fn with_connection<T>(
+        &self,
+        mut op: impl FnMut(&mut FtpStream) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut ftp_stream = self.acquire()?;
+
+        match op(&mut ftp_stream) {
+            Ok(value) => {
+                self.release(ftp_stream);
+                Ok(value)
+            }
+            Err(e) if Self::is_stale_connection_error(&e) => {
+                warn!(
+                    "Pooled FTP connection appears stale ({}); reconnecting and retrying once",
+                    e
+                );
+                // 失効した接続を捨て、他の idle 接続ではなく新規に接続し直す。枠 (in_use) は引き継ぐ
+                drop(ftp_stream);
+                let mut fresh_stream = match self.sender.connect_and_login() {
+                    Ok(ftp_stream) => ftp_stream,
+                    Err(e) => {
+                        self.discard();
+                        return Err(e);
+                    }
+                };
+                match op(&mut fresh_stream) {
+                    Ok(value) => {
+                        self.release(fresh_stream);
+                        Ok(value)
+                    }
+                    Err(e) => {
+                        // 再試行後の接続も、成否にかかわらず必ず release/discard して
+                        // in_use を戻す。ここで `?` を使うと枠が永遠にリークしてしまう
+                        if Self::is_stale_connection_error(&e) {
+                            self.discard();
+                        } else {
+                            self.release(fresh_stream);
+                        }
+                        Err(e)
+                    }
+                }
+            }
+            Err(e) => {
+                self.release(ftp_stream);
+                Err(e)
+            }
+        }
+    }
+
+    /// エラーが、接続自体が失効・切断されたことに起因するものかどうかを判定する。
+    /// ローカルファイルの不備など接続とは無関係なエラーでプールの接続を無駄に
+    /// 破棄しないよう、再試行の可否をこの判定で絞り込む
+    ///
+    /// ローカルファイル操作の `std::io::Error` は対象外。`FtpError` のうち、サーバーが 5xx の
+    /// 応答を返した (= 接続自体は生きている) ものも対象外とする
+    fn is_stale_connection_error(error: &(dyn Error + 'static)) -> bool {
+        match error.downcast_ref::<FtpError>() {
+            Some(FtpError::ConnectionError(_)) => true,
+            Some(FtpError::InvalidResponse(msg)) => !msg.contains("got response: 5"),
+            _ => false,
+        }
+    }
+
+    /// 空いている接続を 1 本取得する。無ければ上限に達するまで新規に接続し、
+    /// 上限に達している場合は空くまで待機する
+    fn acquire(&self) -> Result<FtpStream, Box<dyn Error>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(ftp_stream) = state.idle.pop() {
+                state.in_use += 1;
+                return Ok(ftp_stream);
+            }
+
+            if state.in_use < self.capacity {
+                state.in_use += 1;
+                drop(state);
+                info!("Opening a new pooled FTP connection");
+                match self.sender.connect_and_login() {
+                    Ok(ftp_stream) => return Ok(ftp_stream),
+                    Err(e) => {
+                        // 接続に失敗した分の枠を解放しないと in_use が永遠に嵩上げされたままになり、
+                        // 以後の acquire() が上限に達したと誤認して無期限にブロックしてしまう
+                        self.discard();
+                        return Err(e);
+                    }
+                }
+            }
+
+            state = self.available.wait(state).unwrap();
+        }
+    }
+
+    /// 接続をプールへ返却し、待機中の利用者に通知する
+    fn release(&self, ftp_stream: FtpStream) {
+        let mut state = self.state.lock().unwrap();
+        state.in_use -= 1;
+        state.idle.push(ftp_stream);
+        drop(state);
+        self.available.notify_one();
+    }
+
+    /// 使い物にならなくなった接続を、プールへ戻さずに破棄する
+    fn discard(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.in_use -= 1;
+        drop(state);
+        self.available.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dummy_sender() -> FtpSender {
+        FtpSender::new("127.0.0.1", 21, 5.0, None, None)
+    }
+
+    #[test]
+    fn new_clamps_a_zero_capacity_up_to_one() {
+        let pool = FtpPool::new(dummy_sender(), 0);
+        assert_eq!(pool.capacity, 1);
+    }
+
+    #[test]
+    fn new_keeps_a_positive_capacity_as_is() {
+        let pool = FtpPool::new(dummy_sender(), 4);
+        assert_eq!(pool.capacity, 4);
+    }
+}
@@ -1,98 +1,633 @@
-use ftp::FtpStream;
-use log::{error, info};
-use std::error::Error;
-use std::fs::File;
-use std::path::{Path, PathBuf};
-use std::time::Duration;
-
-/// FTP 送信機能を提供する構造体
-pub struct FtpSender {
-    host: String,
-    port: u16,
-    timeout: Duration,
-    username: Option<String>,
-    password: Option<String>,
-}
-
-impl FtpSender {
-    /// 新しい FtpSender を作成する
-    pub fn new(
-        host: &str,
-        port: u16,
-        timeout_secs: f64,
-        username: Option<&str>,
-        password: Option<&str>,
-    ) -> Self {
-        FtpSender {
-            host: host.to_string(),
-            port,
-            timeout: Duration::from_secs_f64(timeout_secs),
-            username: username.map(|s: &str| s.to_string()),
-            password: password.map(|s: &str| s.to_string()),
-        }
-    }
-
-    /// FTP サーバーに接続し、ログイン後、指定されたファイルを指定のフォルダーに送信する
-    pub fn send_file(
-        &self,
-        source_file_path: &str,
-        target_folder: &str,
-    ) -> Result<(), Box<dyn Error>> {
-        info!("Attempting to send file via FTP...");
-
-        // ファイルパスを正規化して、ディレクトリトラバーサル攻撃対策
-        let source_path: PathBuf = std::fs::canonicalize(source_file_path)?;
-        if !source_path.is_file() {
-            let err_msg: String = format!(
-                "Source file '{}' does not exist or is not a file.",
-                source_file_path
-            );
-            error!("{}", err_msg);
-            return Err(err_msg.into());
-        }
-
-        // 送信するファイル名を取得
-        let filename: &std::ffi::OsStr = source_path
-            .file_name()
-            .ok_or("Failed to get the source file name")?;
-        let target_file_path = Path::new(target_folder).join(filename);
-        let target_file_path_str = target_file_path.to_string_lossy();
-
-        // FTP サーバへの接続
-        let addr: String = format!("{}:{}", self.host, self.port);
-        let mut ftp_stream: FtpStream = FtpStream::connect(addr)?;
-        ftp_stream.get_ref().set_read_timeout(Some(self.timeout))?;
-        ftp_stream.get_ref().set_write_timeout(Some(self.timeout))?;
-        info!("Connected to {} on port {}", self.host, self.port);
-
-        // ログイン処理
-        match (&self.username, &self.password) {
-            (Some(user), Some(pass)) => {
-                ftp_stream.login(user, pass)?;
-                info!("Login successful with provided credentials");
-            }
-            _ => {
-                ftp_stream.login("anonymous", "anonymous")?;
-                info!("Anonymous login successful");
-            }
-        }
-
-        // ファイル送信
-        info!(
-            "Sending file '{}' to folder '{}' on the server",
-            source_file_path, target_folder
-        );
-        let mut file: File = File::open(&source_path)?;
-        ftp_stream.put(&target_file_path_str, &mut file)?;
-        info!(
-            "File '{}' sent successfully to folder '{}'",
-            source_file_path, target_folder
-        );
-
-        // 接続終了
-        ftp_stream.quit()?;
-        info!("FTP connection closed");
-
-        Ok(())
-    }
-}
+use ftp::types::FileType;
+use ftp::{FtpError, FtpStream};
+#[cfg(feature = "secure")]
+use openssl::ssl::{SslContext, SslMethod};
+use log::{error, info, warn};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// アップロード時にローカルファイルを読み出す `BufReader` のバッファサイズ。ファイル全体をメモリに載せないための固定バッファ
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// SIZE 問い合わせの結果を踏まえて、再開アップロードをどう扱うかの判定結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResumeDecision {
+    /// リモートに既に同じサイズのファイルがあるため、送信は不要
+    AlreadyComplete,
+    /// 先頭から送信する (リモートが存在しない、またはローカルとサイズが異なる)
+    UploadFromStart,
+}
+
+/// `Read` を透過的にラップし、読み取りが発生するたびに `progress` コールバックへ
+/// (これまでの累計バイト数, 全体バイト数) を通知するアダプタ。下層の `ftp` クレートの
+/// `put` は内部で読み取りバッファ単位に `Read::read` を呼び出すため、ここでチャンクごとの
+/// 進捗を観測できる
+struct ProgressReader<'a, R: Read> {
+    inner: R,
+    total_size: u64,
+    sent: u64,
+    progress: Option<&'a mut (dyn FnMut(u64, u64) + 'a)>,
+}
+
+impl<'a, R: Read> Read for ProgressReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read: usize = self.inner.read(buf)?;
+        if read > 0 {
+            self.sent += read as u64;
+            if let Some(cb) = self.progress.as_deref_mut() {
+                cb(self.sent, self.total_size);
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// `remote_size` (SIZE コマンドの結果) と `local_size` からアップロードの再開方針を決定する。
+/// 下層の `ftp` クレートは REST によるオフセット再開をサポートしないため、
+/// 行えるのは「完全に一致する場合のスキップ」だけであり、それ以外は常に先頭から送信する
+fn decide_resume(remote_size: Option<u64>, local_size: u64) -> ResumeDecision {
+    match remote_size {
+        Some(remote_size) if remote_size == local_size => ResumeDecision::AlreadyComplete,
+        _ => ResumeDecision::UploadFromStart,
+    }
+}
+
+/// FTPS (FTP over TLS) の接続方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TlsMode {
+    /// TLS を使用しない平文の FTP 接続
+    #[default]
+    None,
+    /// 平文で接続した後、AUTH TLS で制御/データ接続を暗号化する (Explicit FTPS)
+    Explicit,
+    /// 接続した時点で TLS により保護されている (Implicit FTPS)。
+    /// 現時点では `ftp` クレートが plaintext connect からのアップグレードしかサポートしないため未対応で、
+    /// `secure` フィーチャー有効時に指定すると `connect_and_login` がエラーを返す。
+    /// フィーチャー無効時は `Explicit` と同様にエラーログを出して平文の FTP で接続する
+    Implicit,
+}
+
+/// データ接続の確立方式
+///
+/// 下層の `ftp` クレートは PASV によるデータ接続しかサポートしておらず、能動的に
+/// リスニングソケットを開いて `PORT`/`EPRT` を送る手段を公開していないため、
+/// `Active` を指定すると `connect_and_login` は未対応エラーを返す。
+///
+/// なお「PASV 応答で返されるホストを上書きしたい (NAT 環境向け)」という要望も一部にあるが、
+/// `ftp` クレートは PASV 応答を解釈する `pasv()` を非公開にしており、アプリケーション側から
+/// 観測・上書きするフックを一切公開していない。そのため本クレートでもサポートしておらず、
+/// `FtpSender::with_pasv_host_override` は警告を出すだけの no-op になっている
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransferMode {
+    /// サーバー側がデータ接続を開始する (PASV)。`ftp` クレートが実装している唯一の方式で、既定値
+    #[default]
+    Passive,
+    /// クライアント側がリスニングソケットを用意し、サーバーへ PORT で通知する (Active)。未対応
+    Active,
+}
+
+/// FTP 送信機能を提供する構造体
+pub struct FtpSender {
+    host: String,
+    port: u16,
+    timeout: Duration,
+    username: Option<String>,
+    password: Option<String>,
+    tls_mode: TlsMode,
+    follow_symlinks: bool,
+    resume: bool,
+    transfer_mode: TransferMode,
+}
+
+impl FtpSender {
+    /// 新しい FtpSender を作成する
+    pub fn new(
+        host: &str,
+        port: u16,
+        timeout_secs: f64,
+        username: Option<&str>,
+        password: Option<&str>,
+    ) -> Self {
+        FtpSender {
+            host: host.to_string(),
+            port,
+            timeout: Duration::from_secs_f64(timeout_secs),
+            username: username.map(|s: &str| s.to_string()),
+            password: password.map(|s: &str| s.to_string()),
+            tls_mode: TlsMode::None,
+            follow_symlinks: false,
+            resume: false,
+            transfer_mode: TransferMode::Passive,
+        }
+    }
+
+    /// Explicit FTPS (AUTH TLS) を有効にする
+    pub fn enable_secure(mut self) -> Self {
+        self.tls_mode = TlsMode::Explicit;
+        self
+    }
+
+    /// FTPS の接続方式を明示的に指定する (平文 / Explicit / Implicit)
+    pub fn with_tls_mode(mut self, mode: TlsMode) -> Self {
+        self.tls_mode = mode;
+        self
+    }
+
+    /// ディレクトリ送信時にシンボリックリンクを辿るかどうかを設定する (既定では辿らずスキップする)
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// SIZE を使った再送スキップを有効にする。リモートに既に同じサイズのファイルがあれば
+    /// 送信をスキップするが、下層の `ftp` クレートに REST オフセット API が無いため
+    /// 部分的に送信済みのファイルの「続きから送信」はサポートしない (常に先頭から送り直す)。
+    ///
+    /// そのため、フラッキーな回線で大きなファイルの転送が途中で切断された場合に
+    /// 「残りだけ送って再開する」という本来の目的は、この実装では解決できていない
+    /// (切断された転送は、次回実行時に毎回サイズの差分を検知して最初から送り直すだけになる)。
+    /// 真の意味での再開を行うには、下層クレートの置き換え、または PASV データ接続後に
+    /// 自前で `REST` コマンドを発行する実装が別途必要になる
+    pub fn enable_resume(mut self) -> Self {
+        self.resume = true;
+        self
+    }
+
+    /// データ接続の確立方式 (Passive / Active) を指定する。`Active` は現時点では
+    /// `ftp` クレートが対応していないため、接続時にエラーとなる
+    pub fn with_transfer_mode(mut self, mode: TransferMode) -> Self {
+        self.transfer_mode = mode;
+        self
+    }
+
+    /// PASV 応答で返されるホストをこの値で上書きしたいという要望 (NAT 環境向け) に対応するための
+    /// builder。下層の `ftp` クレートは PASV 応答を解釈する `pasv()` を非公開にしており、
+    /// アプリケーション側から観測・上書きするフックが存在しないため、実際には何も行わず
+    /// 警告を出すだけの no-op になっている
+    pub fn with_pasv_host_override(self, _host: &str) -> Self {
+        warn!(
+            "Overriding the PASV reply host was requested, but the underlying 'ftp' crate exposes no hook for it; ignoring"
+        );
+        self
+    }
+
+    /// FTP サーバーへ接続し、必要であれば TLS へアップグレードしてログインする
+    pub(crate) fn connect_and_login(&self) -> Result<FtpStream, Box<dyn Error>> {
+        // サポートしていない設定は、TCP 接続 (および TLS ハンドシェイク) という
+        // ネットワーク往復を無駄に払う前に弾く。`TlsMode::Implicit` は (`secure` フィーチャー有効時)
+        // 下の match 内で接続前に弾かれるが、`TransferMode::Active` はここで先に検証しておく
+        if self.transfer_mode == TransferMode::Active {
+            let err_msg =
+                "Active (PORT) transfer mode is not supported by the underlying 'ftp' crate; use TransferMode::Passive instead";
+            error!("{}", err_msg);
+            return Err(err_msg.into());
+        }
+
+        let addr: String = format!("{}:{}", self.host, self.port);
+
+        #[cfg(feature = "secure")]
+        let mut ftp_stream: FtpStream = match self.tls_mode {
+            TlsMode::None => FtpStream::connect(&addr)?,
+            TlsMode::Explicit => {
+                info!("Upgrading control connection to TLS (AUTH TLS)");
+                let ssl_context: SslContext = SslContext::builder(SslMethod::tls())?.build();
+                FtpStream::connect(&addr)?.into_secure(ssl_context)?
+            }
+            TlsMode::Implicit => {
+                // The underlying `ftp` crate always dials a plaintext socket first and only
+                // supports upgrading it via AUTH TLS (`into_secure`); it has no way to dial a
+                // TLS socket from the first byte, so implicit FTPS cannot be offered here.
+                let err_msg =
+                    "Implicit FTPS is not supported by the underlying 'ftp' crate; use TlsMode::Explicit instead";
+                error!("{}", err_msg);
+                return Err(err_msg.into());
+            }
+        };
+
+        #[cfg(not(feature = "secure"))]
+        let mut ftp_stream: FtpStream = {
+            if self.tls_mode != TlsMode::None {
+                error!("FTPS was requested but the 'secure' feature is not enabled; falling back to plaintext FTP");
+            }
+            FtpStream::connect(&addr)?
+        };
+
+        ftp_stream.get_ref().set_read_timeout(Some(self.timeout))?;
+        ftp_stream.get_ref().set_write_timeout(Some(self.timeout))?;
+        info!("Connected to {} on port {}", self.host, self.port);
+
+        // ログイン処理
+        match (&self.username, &self.password) {
+            (Some(user), Some(pass)) => {
+                ftp_stream.login(user, pass)?;
+                info!("Login successful with provided credentials");
+            }
+            _ => {
+                ftp_stream.login("anonymous", "anonymous")?;
+                info!("Anonymous login successful");
+            }
+        }
+
+        Ok(ftp_stream)
+    }
+
+    /// FTP サーバーに接続し、ログイン後、指定されたファイルを指定のフォルダーに送信する
+    pub fn send_file(
+        &self,
+        source_file_path: &str,
+        target_folder: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut ftp_stream: FtpStream = self.connect_and_login()?;
+        self.send_file_over(&mut ftp_stream, source_file_path, target_folder, None)?;
+        ftp_stream.quit()?;
+        info!("FTP connection closed");
+
+        Ok(())
+    }
+
+    /// `send_file` と同様だが、送信したバイト数を都度 `progress` コールバックへ通知する。
+    /// 成功時は最後に必ず `(total, total)` を通知するので、呼び出し側はこれを完了の合図にできる
+    pub fn send_file_with_progress(
+        &self,
+        source_file_path: &str,
+        target_folder: &str,
+        mut progress: Option<Box<dyn FnMut(u64, u64)>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut ftp_stream: FtpStream = self.connect_and_login()?;
+        self.send_file_over(
+            &mut ftp_stream,
+            source_file_path,
+            target_folder,
+            progress.as_deref_mut(),
+        )?;
+        ftp_stream.quit()?;
+        info!("FTP connection closed");
+
+        Ok(())
+    }
+
+    /// 既に接続・ログイン済みの `ftp_stream` を使って、指定されたファイルを送信する。
+    /// `FtpPool` など、接続の確立を呼び出し側が管理する場合に使われる
+    pub(crate) fn send_file_over<'a>(
+        &self,
+        ftp_stream: &mut FtpStream,
+        source_file_path: &str,
+        target_folder: &str,
+        progress: Option<&'a mut (dyn FnMut(u64, u64) + 'a)>,
+    ) -> Result<(), Box<dyn Error>> {
+        info!("Attempting to send file via FTP...");
+
+        // ファイルパスを正規化して、ディレクトリトラバーサル攻撃対策
+        let source_path: PathBuf = std::fs::canonicalize(source_file_path)?;
+        if !source_path.is_file() {
+            let err_msg: String = format!(
+                "Source file '{}' does not exist or is not a file.",
+                source_file_path
+            );
+            error!("{}", err_msg);
+            return Err(err_msg.into());
+        }
+
+        // 送信するファイル名を取得
+        let filename: &std::ffi::OsStr = source_path
+            .file_name()
+            .ok_or("Failed to get the source file name")?;
+        let target_file_path = Path::new(target_folder).join(filename);
+
+        self.upload_file(
+            ftp_stream,
+            &source_path,
+            &target_file_path.to_string_lossy(),
+            progress,
+        )
+    }
+
+    /// FTP サーバーに接続し、ログイン後、指定されたファイルまたはディレクトリを送信する
+    ///
+    /// `source_path` がディレクトリの場合、配下を深さ優先で再帰的に走査し、
+    /// `target_folder` 以下にリモート側のディレクトリ構造を再現しながら全ファイルを送信する。
+    /// `source_path` がファイルの場合は `send_file` と同じ結果になる。
+    pub fn send_path(
+        &self,
+        source_path: &str,
+        target_folder: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        info!("Attempting to send path via FTP...");
+
+        // パスを正規化して、ディレクトリトラバーサル攻撃対策
+        let source_path: PathBuf = std::fs::canonicalize(source_path)?;
+
+        // FTP サーバへの接続・ログイン
+        let mut ftp_stream: FtpStream = self.connect_and_login()?;
+
+        if source_path.is_dir() {
+            self.ensure_remote_dir_recursive(&mut ftp_stream, target_folder)?;
+            self.send_dir_contents(&mut ftp_stream, &source_path, &source_path, target_folder)?;
+        } else if source_path.is_file() {
+            let filename: &std::ffi::OsStr = source_path
+                .file_name()
+                .ok_or("Failed to get the source file name")?;
+            let target_file_path = Path::new(target_folder).join(filename);
+            self.upload_file(
+                &mut ftp_stream,
+                &source_path,
+                &target_file_path.to_string_lossy(),
+                None,
+            )?;
+        } else {
+            let err_msg: String = format!(
+                "Source path '{}' does not exist or is neither a file nor a directory.",
+                source_path.display()
+            );
+            error!("{}", err_msg);
+            return Err(err_msg.into());
+        }
+
+        // 接続終了
+        ftp_stream.quit()?;
+        info!("FTP connection closed");
+
+        Ok(())
+    }
+
+    /// FTP サーバーに接続し、ログイン後、指定されたリモートファイルを `local_folder` にダウンロードする
+    pub fn get_file(
+        &self,
+        remote_path: &str,
+        local_folder: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut ftp_stream: FtpStream = self.connect_and_login()?;
+        self.get_file_over(&mut ftp_stream, remote_path, local_folder)?;
+        ftp_stream.quit()?;
+        info!("FTP connection closed");
+
+        Ok(())
+    }
+
+    /// 既に接続・ログイン済みの `ftp_stream` を使って、指定されたリモートファイルをダウンロードする。
+    /// `FtpPool` など、接続の確立を呼び出し側が管理する場合に使われる
+    pub(crate) fn get_file_over(
+        &self,
+        ftp_stream: &mut FtpStream,
+        remote_path: &str,
+        local_folder: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        info!("Attempting to receive file via FTP...");
+
+        let local_file_path = Self::local_path_for_remote(local_folder, remote_path)?;
+
+        ftp_stream.transfer_type(FileType::Binary)?;
+
+        info!(
+            "Receiving file '{}' into '{}'",
+            remote_path,
+            local_file_path.display()
+        );
+        // `simple_retr` はファイル全体を `Vec<u8>` に読み込んでから返すため、大きなファイルでは
+        // メモリを圧迫する。`retr` はデータストリームへの `&mut dyn Read` をそのままクロージャへ
+        // 渡すので、`io::copy` で直接ローカルファイルへ書き出せてメモリに載せる必要が無い
+        let mut local_file: File = File::create(&local_file_path)?;
+        ftp_stream.retr(remote_path, |reader| {
+            std::io::copy(reader, &mut local_file)
+                .map(|_| ())
+                .map_err(FtpError::ConnectionError)
+        })?;
+        info!(
+            "File '{}' received successfully into '{}'",
+            remote_path,
+            local_file_path.display()
+        );
+
+        Ok(())
+    }
+
+    /// 指定したリモートフォルダーの内容を一覧取得する
+    pub fn list(&self, remote_folder: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        info!("Listing remote folder '{}'", remote_folder);
+
+        let mut ftp_stream: FtpStream = self.connect_and_login()?;
+        let entries: Vec<String> = ftp_stream.nlst(Some(remote_folder))?;
+
+        ftp_stream.quit()?;
+        info!("FTP connection closed");
+
+        Ok(entries)
+    }
+
+    /// `root` を起点に `current` ディレクトリ以下を深さ優先で走査し、
+    /// `target_folder` を基準としたリモートパスへ各ファイルを送信する
+    fn send_dir_contents(
+        &self,
+        ftp_stream: &mut FtpStream,
+        root: &Path,
+        current: &Path,
+        target_folder: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        for entry in std::fs::read_dir(current)? {
+            let entry = entry?;
+            let path = entry.path();
+            let symlink_metadata = std::fs::symlink_metadata(&path)?;
+
+            if symlink_metadata.file_type().is_symlink() && !self.follow_symlinks {
+                warn!("Skipping symlink '{}'", path.display());
+                continue;
+            }
+
+            let relative = path.strip_prefix(root)?;
+            let remote_path = Self::join_remote_path(target_folder, relative);
+
+            if path.is_dir() {
+                self.ensure_remote_dir(ftp_stream, &remote_path);
+                self.send_dir_contents(ftp_stream, root, &path, target_folder)?;
+            } else if path.is_file() {
+                self.upload_file(ftp_stream, &path, &remote_path, None)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 単一のファイルを開いてリモートパスへ送信する。`resume` が有効な場合は
+    /// バイナリ転送モードを設定したうえで SIZE コマンドでリモートの既存サイズを確認する。
+    /// 下層の `ftp` クレートは REST によるオフセット再開 API を公開していないため、
+    /// 実際に行える最適化は「リモートが既に完全なサイズを持つ場合に送信をスキップする」ことのみで、
+    /// サイズが一致しない場合は先頭から送り直す (`decide_resume` 参照)。
+    /// 下層の `ftp` クレートにはストリーム分割送信用の API が無く `put` で一度に送信するしかないため、
+    /// ファイルは `BufReader` 越しに `UPLOAD_CHUNK_SIZE` 単位で読み出されるが、送信自体は 1 回の
+    /// `put` 呼び出しで完結する。`progress` が与えられていれば `ProgressReader` が読み取りのたびに
+    /// (送信済みバイト数, 全体バイト数) を通知する
+    fn upload_file<'a>(
+        &self,
+        ftp_stream: &mut FtpStream,
+        source_path: &Path,
+        target_file_path: &str,
+        mut progress: Option<&'a mut (dyn FnMut(u64, u64) + 'a)>,
+    ) -> Result<(), Box<dyn Error>> {
+        let file: File = File::open(source_path)?;
+        let total_size: u64 = file.metadata()?.len();
+
+        if self.resume {
+            ftp_stream.transfer_type(FileType::Binary)?;
+
+            let remote_size: Option<u64> = match ftp_stream.size(target_file_path) {
+                Ok(size) => size.map(|s| s as u64),
+                Err(e) => {
+                    warn!(
+                        "Could not query remote size of '{}' ({}); starting a fresh upload",
+                        target_file_path, e
+                    );
+                    None
+                }
+            };
+
+            match decide_resume(remote_size, total_size) {
+                ResumeDecision::AlreadyComplete => {
+                    info!(
+                        "Remote file '{}' already has the full size ({} bytes); skipping",
+                        target_file_path, total_size
+                    );
+                    if let Some(cb) = progress.as_deref_mut() {
+                        cb(total_size, total_size);
+                    }
+                    return Ok(());
+                }
+                ResumeDecision::UploadFromStart => {
+                    if let Some(remote_size) = remote_size {
+                        warn!(
+                            "Remote file '{}' does not match the local file size ({} vs {} bytes); \
+                             the underlying 'ftp' crate has no REST/offset API, restarting from zero",
+                            target_file_path, remote_size, total_size
+                        );
+                    }
+                }
+            }
+        }
+
+        info!(
+            "Sending file '{}' to '{}' on the server",
+            source_path.display(),
+            target_file_path
+        );
+
+        let mut reader = ProgressReader {
+            inner: BufReader::with_capacity(UPLOAD_CHUNK_SIZE, file),
+            total_size,
+            sent: 0,
+            progress,
+        };
+        ftp_stream.put(target_file_path, &mut reader)?;
+
+        if let Some(cb) = reader.progress.as_deref_mut() {
+            cb(total_size, total_size);
+        }
+
+        info!(
+            "File '{}' sent successfully to '{}'",
+            source_path.display(),
+            target_file_path
+        );
+
+        Ok(())
+    }
+
+    /// リモートディレクトリを 1 階層分作成する。既に存在する場合のエラーは無視する
+    fn ensure_remote_dir(&self, ftp_stream: &mut FtpStream, remote_dir: &str) {
+        match ftp_stream.mkdir(remote_dir) {
+            Ok(()) => info!("Created remote directory '{}'", remote_dir),
+            Err(e) => warn!(
+                "Could not create remote directory '{}' (it may already exist): {}",
+                remote_dir, e
+            ),
+        }
+    }
+
+    /// リモートディレクトリを根本から順に作成する (中間ディレクトリをまとめて作成する)
+    fn ensure_remote_dir_recursive(
+        &self,
+        ftp_stream: &mut FtpStream,
+        remote_dir: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut path = String::new();
+        if remote_dir.starts_with('/') {
+            path.push('/');
+        }
+        for component in remote_dir.split('/').filter(|c| !c.is_empty()) {
+            path.push_str(component);
+            self.ensure_remote_dir(ftp_stream, &path);
+            path.push('/');
+        }
+
+        Ok(())
+    }
+
+    /// `remote_path` のファイル名部分を取り出し、`local_folder` 配下に結合したローカルパスを組み立てる
+    fn local_path_for_remote(local_folder: &str, remote_path: &str) -> Result<PathBuf, Box<dyn Error>> {
+        let filename = Path::new(remote_path)
+            .file_name()
+            .ok_or("Failed to get the remote file name")?;
+        Ok(Path::new(local_folder).join(filename))
+    }
+
+    /// `target_folder` に相対パス `relative` を連結したリモートパスを組み立てる (常に '/' 区切り)
+    fn join_remote_path(target_folder: &str, relative: &Path) -> String {
+        let mut remote = target_folder.trim_end_matches('/').to_string();
+        for component in relative.components() {
+            remote.push('/');
+            remote.push_str(&component.as_os_str().to_string_lossy());
+        }
+        remote
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_remote_path_appends_nested_components_with_slash() {
+        let relative = Path::new("sub/dir/file.txt");
+        assert_eq!(
+            FtpSender::join_remote_path("/uploads", relative),
+            "/uploads/sub/dir/file.txt"
+        );
+    }
+
+    #[test]
+    fn join_remote_path_strips_trailing_slash_from_target() {
+        let relative = Path::new("file.txt");
+        assert_eq!(
+            FtpSender::join_remote_path("/uploads/", relative),
+            "/uploads/file.txt"
+        );
+    }
+
+    #[test]
+    fn decide_resume_skips_when_remote_size_matches() {
+        assert_eq!(decide_resume(Some(100), 100), ResumeDecision::AlreadyComplete);
+    }
+
+    #[test]
+    fn decide_resume_restarts_when_remote_size_differs() {
+        assert_eq!(decide_resume(Some(50), 100), ResumeDecision::UploadFromStart);
+    }
+
+    #[test]
+    fn decide_resume_restarts_when_remote_file_is_missing() {
+        assert_eq!(decide_resume(None, 100), ResumeDecision::UploadFromStart);
+    }
+
+    #[test]
+    fn local_path_for_remote_joins_folder_and_filename() {
+        let local_path = FtpSender::local_path_for_remote("./downloads", "/remote/dir/file.txt")
+            .expect("remote path has a file name");
+        assert_eq!(local_path, Path::new("./downloads/file.txt"));
+    }
+
+    #[test]
+    fn local_path_for_remote_rejects_a_path_with_no_file_name() {
+        assert!(FtpSender::local_path_for_remote("./downloads", "/").is_err());
+    }
+}